@@ -20,9 +20,24 @@ pub struct Args {
     #[arg(long)]
     pub watch: Option<u64>,
 
+    /// Public IP to geolocate, instead of looking ours up (used by the ip/mmdb providers).
+    ///
+    /// Note: the mmdb provider is only fully offline when this is set; if it is
+    /// omitted the public IP is still discovered via a network request.
+    #[arg(long)]
+    pub ip: Option<String>,
+
+    /// Path to a MaxMind GeoLite2/GeoIP2 City database for the mmdb provider.
+    #[arg(long, default_value = "/usr/share/GeoIP/GeoLite2-City.mmdb")]
+    pub mmdb: String,
+
     #[arg(long)]
     pub no_cache: bool,
 
+    /// Treat a cached fix as fresh for this many seconds.
+    #[arg(long, default_value = "300")]
+    pub max_age: u64,
+
     #[arg(long)]
     pub verbose: bool,
 }
@@ -33,6 +48,7 @@ pub enum Format {
     Csv,
     Env,
     Plain,
+    Gpx,
 }
 
 #[derive(Clone, ValueEnum)]
@@ -40,5 +56,7 @@ pub enum Provider {
     Auto,
     Corelocation,
     Geoclue,
+    Portal,
     Ip,
+    Mmdb,
 }
@@ -0,0 +1,53 @@
+//! Disk-backed cache of the last fix, so repeated shell/prompt invocations
+//! don't pay provider latency while the fix is still fresh.
+
+use crate::location::Location;
+use chrono::Utc;
+use std::path::PathBuf;
+
+/// `$XDG_CACHE_HOME/geo-loc/<key>.json`, falling back to `~/.cache`.
+///
+/// The cache is keyed by the requested provider (e.g. `mmdb`, `ip`, `auto`) so
+/// a fresh fix from one provider is never served for a different `--provider`.
+fn cache_file(key: &str) -> Option<PathBuf> {
+    let base = match std::env::var_os("XDG_CACHE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".cache"),
+    };
+    Some(base.join("geo-loc").join(format!("{key}.json")))
+}
+
+/// Return the fix cached for `key` if it exists and is younger than `max_age`
+/// seconds.
+pub fn load(key: &str, max_age: u64) -> Option<Location> {
+    let path = cache_file(key)?;
+    let bytes = std::fs::read(path).ok()?;
+    let loc: Location = serde_json::from_slice(&bytes).ok()?;
+
+    let age = Utc::now().signed_duration_since(loc.timestamp).num_seconds();
+    if age >= 0 && (age as u64) <= max_age {
+        Some(loc)
+    } else {
+        None
+    }
+}
+
+/// Write `loc` to the cache under `key`, atomically (temp file then rename).
+pub fn store(key: &str, loc: &Location) {
+    let Some(path) = cache_file(key) else {
+        return;
+    };
+    if let Some(parent) = path.parent() {
+        if std::fs::create_dir_all(parent).is_err() {
+            return;
+        }
+    }
+
+    let Ok(json) = serde_json::to_vec(loc) else {
+        return;
+    };
+    let tmp = path.with_extension(format!("json.{}.tmp", std::process::id()));
+    if std::fs::write(&tmp, &json).is_ok() {
+        let _ = std::fs::rename(&tmp, &path);
+    }
+}
@@ -0,0 +1,133 @@
+//! Rendering of a [`Location`] into the CLI's declared output formats.
+
+use crate::args::Format;
+use crate::location::Location;
+
+/// Render a single fix in the requested format.
+pub fn render(loc: &Location, format: &Format) -> String {
+    match format {
+        Format::Json => serde_json::to_string(loc).unwrap_or_default(),
+        Format::Csv => format!(
+            "latitude,longitude,accuracy_m,provider,timestamp\n{},{},{},{},{}",
+            loc.latitude,
+            loc.longitude,
+            accuracy_field(loc),
+            loc.provider,
+            loc.timestamp.to_rfc3339()
+        ),
+        Format::Env => format!(
+            "GEO_LAT={}\nGEO_LON={}\nGEO_ACCURACY_M={}\nGEO_PROVIDER={}\nGEO_TIMESTAMP={}",
+            loc.latitude,
+            loc.longitude,
+            accuracy_field(loc),
+            loc.provider,
+            loc.timestamp.to_rfc3339()
+        ),
+        Format::Plain => format!("{} {}", loc.latitude, loc.longitude),
+        // A standalone fix is still a valid one-point track.
+        Format::Gpx => {
+            let mut doc = gpx_header();
+            doc.push_str(&gpx_trackpoint(loc));
+            doc.push_str(&gpx_footer());
+            doc
+        }
+    }
+}
+
+fn accuracy_field(loc: &Location) -> String {
+    loc.accuracy_m
+        .map(|a| a.to_string())
+        .unwrap_or_default()
+}
+
+pub fn gpx_header() -> String {
+    "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+     <gpx version=\"1.1\" creator=\"geo-loc\" \
+     xmlns=\"http://www.topografix.com/GPX/1/1\" \
+     xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" \
+     xsi:schemaLocation=\"http://www.topografix.com/GPX/1/1 \
+     http://www.topografix.com/GPX/1/1/gpx.xsd\"><trk><trkseg>\n"
+        .to_string()
+}
+
+pub fn gpx_trackpoint(loc: &Location) -> String {
+    let mut pt = format!(
+        "<trkpt lat=\"{}\" lon=\"{}\"><time>{}</time>",
+        loc.latitude,
+        loc.longitude,
+        loc.timestamp.to_rfc3339()
+    );
+    // Providers report a horizontal radius in metres; surface it as an
+    // HDOP-style hint rather than claiming true dilution of precision.
+    if let Some(acc) = loc.accuracy_m {
+        pt.push_str(&format!("<hdop>{}</hdop><!-- accuracy_m={} -->", acc, acc));
+    }
+    pt.push_str("</trkpt>\n");
+    pt
+}
+
+pub fn gpx_footer() -> String {
+    "</trkseg></trk></gpx>\n".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Utc};
+
+    fn sample() -> Location {
+        let ts: DateTime<Utc> = "2024-01-02T03:04:05Z".parse().unwrap();
+        Location::new(12.5, -7.25, Some(30.0), "geoclue", ts)
+    }
+
+    #[test]
+    fn plain_is_lat_lon() {
+        assert_eq!(render(&sample(), &Format::Plain), "12.5 -7.25");
+    }
+
+    #[test]
+    fn csv_has_header_and_row() {
+        assert_eq!(
+            render(&sample(), &Format::Csv),
+            "latitude,longitude,accuracy_m,provider,timestamp\n\
+             12.5,-7.25,30,geoclue,2024-01-02T03:04:05+00:00"
+        );
+    }
+
+    #[test]
+    fn env_emits_shell_vars() {
+        let out = render(&sample(), &Format::Env);
+        assert!(out.contains("GEO_LAT=12.5"));
+        assert!(out.contains("GEO_LON=-7.25"));
+        assert!(out.contains("GEO_ACCURACY_M=30"));
+        assert!(out.contains("GEO_PROVIDER=geoclue"));
+    }
+
+    #[test]
+    fn json_round_trips() {
+        let out = render(&sample(), &Format::Json);
+        let back: Location = serde_json::from_str(&out).unwrap();
+        assert_eq!(back.provider, "geoclue");
+        assert_eq!(back.latitude, 12.5);
+    }
+
+    #[test]
+    fn gpx_is_a_single_point_track() {
+        let out = render(&sample(), &Format::Gpx);
+        assert!(out.starts_with("<?xml"));
+        assert!(out.contains("<trkpt lat=\"12.5\" lon=\"-7.25\">"));
+        assert!(out.contains("<hdop>30</hdop>"));
+        assert!(out.trim_end().ends_with("</gpx>"));
+    }
+
+    #[test]
+    fn missing_accuracy_renders_empty_field() {
+        let ts: DateTime<Utc> = "2024-01-02T03:04:05Z".parse().unwrap();
+        let loc = Location::new(1.0, 2.0, None, "ip", ts);
+        assert_eq!(
+            render(&loc, &Format::Csv),
+            "latitude,longitude,accuracy_m,provider,timestamp\n\
+             1,2,,ip,2024-01-02T03:04:05+00:00"
+        );
+    }
+}
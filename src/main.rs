@@ -2,13 +2,51 @@
 //!
 //! Tries GeoClue first, falls back to IP geolocation
 
+mod args;
+mod cache;
+mod format;
+mod location;
+mod providers;
+
+use std::io::Write;
 use std::process;
 
+use args::{Args, Format, Provider};
+use chrono::Utc;
+use clap::Parser;
+use location::Location;
+
 #[tokio::main]
 async fn main() {
-    match get_location().await {
-        Ok((lat, lon)) => {
-            println!("{} {}", lat, lon);
+    let args = Args::parse();
+
+    if let Some(interval) = args.watch {
+        if let Err(e) = watch(&args, interval).await {
+            eprintln!("geo-loc: {}", e.message);
+            process::exit(e.code);
+        }
+        return;
+    }
+
+    // A fresh cached fix short-circuits the provider round-trip. The entry is
+    // keyed by the requested provider so `--provider` is always honored.
+    let cache_key = provider_key(&args.provider);
+    if !args.no_cache {
+        if let Some(loc) = cache::load(cache_key, args.max_age) {
+            if args.verbose {
+                eprintln!("geo-loc: cache hit ({})", loc.provider);
+            }
+            print_once(&loc, &args.format);
+            return;
+        }
+    }
+
+    match get_location(&args).await {
+        Ok(loc) => {
+            if !args.no_cache {
+                cache::store(cache_key, &loc);
+            }
+            print_once(&loc, &args.format);
         }
         Err(e) => {
             eprintln!("geo-loc: {}", e.message);
@@ -17,22 +55,192 @@ async fn main() {
     }
 }
 
-async fn get_location() -> Result<(f64, f64), Error> {
-    // Try GeoClue first
-    match try_geoclue().await {
-        Ok(coords) => Ok(coords),
-        Err(e) => {
-            // Show helpful message for permission errors, then fall back to IP
-            if e.code == 77 {
-                eprintln!("geo-loc: {}", e.message);
-                eprintln!("geo-loc: falling back to IP-based location...");
-            }
-            try_ip_location().await
+fn print_once(loc: &Location, fmt: &Format) {
+    match fmt {
+        // Stream the one-point track without a trailing blank line.
+        Format::Gpx => {
+            let mut out = std::io::stdout();
+            let _ = out.write_all(format::render(loc, fmt).as_bytes());
+            let _ = out.flush();
+        }
+        _ => println!("{}", format::render(loc, fmt)),
+    }
+}
+
+/// Poll the selected provider every `interval` seconds until interrupted.
+///
+/// In `--format gpx` mode the track header is emitted once up front, a
+/// `<trkpt>` is appended and flushed after each fix so a reader tailing the
+/// stream sees live updates, and the track is closed on SIGINT.
+async fn watch(args: &Args, interval: u64) -> Result<(), Error> {
+    let interval = std::time::Duration::from_secs(interval.max(1));
+    let gpx = matches!(args.format, Format::Gpx);
+    let mut out = std::io::stdout();
+
+    if gpx {
+        out.write_all(format::gpx_header().as_bytes()).ok();
+        out.flush().ok();
+    }
+
+    loop {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => break,
+            fix = get_location(args) => match fix {
+                Ok(loc) if gpx => {
+                    out.write_all(format::gpx_trackpoint(&loc).as_bytes()).ok();
+                    out.flush().ok();
+                }
+                Ok(loc) => println!("{}", format::render(&loc, &args.format)),
+                Err(e) => eprintln!("geo-loc: {}", e.message),
+            },
+        }
+
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => break,
+            _ = tokio::time::sleep(interval) => {}
+        }
+    }
+
+    if gpx {
+        out.write_all(format::gpx_footer().as_bytes()).ok();
+        out.flush().ok();
+    }
+
+    Ok(())
+}
+
+/// Stable cache key for the requested provider.
+fn provider_key(provider: &Provider) -> &'static str {
+    match provider {
+        Provider::Auto => "auto",
+        Provider::Corelocation => "corelocation",
+        Provider::Geoclue => "geoclue",
+        Provider::Portal => "portal",
+        Provider::Ip => "ip",
+        Provider::Mmdb => "mmdb",
+    }
+}
+
+/// Fetch a fix from the provider(s) selected by `--provider`.
+async fn get_location(args: &Args) -> Result<Location, Error> {
+    match args.provider {
+        Provider::Auto => auto_chain(args).await,
+        Provider::Corelocation => try_corelocation(args).await,
+        Provider::Geoclue => try_geoclue(args).await,
+        #[cfg(target_os = "linux")]
+        Provider::Portal => try_portal(args).await,
+        #[cfg(not(target_os = "linux"))]
+        Provider::Portal => Err(Error {
+            message: "portal provider is only available on Linux".into(),
+            code: 1,
+        }),
+        Provider::Ip => try_ip_location(args.ip.as_deref()).await,
+        Provider::Mmdb => try_mmdb(args).await,
+    }
+}
+
+/// Run every available provider concurrently within the `--timeout` budget and
+/// return the fix with the smallest accuracy radius, rather than whichever
+/// provider happens to answer first. Returns early as soon as a provider meets
+/// the `--accuracy` target the user asked for.
+async fn auto_chain(args: &Args) -> Result<Location, Error> {
+    use futures_util::stream::{FuturesUnordered, StreamExt};
+    use std::future::Future;
+    use std::pin::Pin;
+
+    let target = accuracy_target_m(args.accuracy.as_deref());
+
+    let mut providers: FuturesUnordered<
+        Pin<Box<dyn Future<Output = Result<Location, Error>> + '_>>,
+    > = FuturesUnordered::new();
+    #[cfg(target_os = "macos")]
+    providers.push(Box::pin(try_corelocation(args)));
+    #[cfg(target_os = "linux")]
+    {
+        providers.push(Box::pin(try_geoclue(args)));
+        providers.push(Box::pin(try_portal(args)));
+    }
+    providers.push(Box::pin(try_ip_location(args.ip.as_deref())));
+
+    let budget = tokio::time::sleep(std::time::Duration::from_secs(args.timeout));
+    tokio::pin!(budget);
+
+    let mut best: Option<Location> = None;
+    loop {
+        tokio::select! {
+            _ = &mut budget => break,
+            next = providers.next() => match next {
+                Some(Ok(loc)) => {
+                    let acc = effective_accuracy(&loc);
+                    // Good enough — don't wait on slower providers.
+                    if target.is_some_and(|t| acc <= t) {
+                        if args.verbose {
+                            eprintln!("geo-loc: {} meets accuracy target ({acc} m)", loc.provider);
+                        }
+                        return Ok(loc);
+                    }
+                    if best.as_ref().is_none_or(|b| acc < effective_accuracy(b)) {
+                        best = Some(loc);
+                    }
+                }
+                Some(Err(_)) => {}
+                None => break, // every provider has reported
+            },
         }
     }
+
+    best.ok_or_else(Error::network)
 }
 
-async fn try_geoclue() -> Result<(f64, f64), Error> {
+/// Comparable accuracy radius in metres: the coarse IP fix gets a large default
+/// radius, and a missing radius sorts worst.
+fn effective_accuracy(loc: &Location) -> f64 {
+    match loc.accuracy_m {
+        Some(a) => a,
+        None if loc.provider == "ip" => 50_000.0,
+        None => f64::MAX,
+    }
+}
+
+/// Parse the `--accuracy` request into a target radius in metres, accepting
+/// either a bare number or a GeoClue-style level name.
+fn accuracy_target_m(accuracy: Option<&str>) -> Option<f64> {
+    let raw = accuracy?;
+    if let Ok(m) = raw.parse::<f64>() {
+        return Some(m);
+    }
+    Some(match raw.to_ascii_lowercase().as_str() {
+        "exact" => 10.0,
+        "street" => 100.0,
+        "neighborhood" | "neighbourhood" => 500.0,
+        "city" => 5_000.0,
+        "country" => 50_000.0,
+        _ => return None,
+    })
+}
+
+async fn try_corelocation(args: &Args) -> Result<Location, Error> {
+    let timeout = std::time::Duration::from_secs(args.timeout);
+    providers::corelocation::get_current_location(timeout, args.verbose)
+        .await
+        .map_err(|e| Error {
+            message: e.to_string(),
+            code: 1,
+        })
+}
+
+#[cfg(target_os = "linux")]
+async fn try_portal(args: &Args) -> Result<Location, Error> {
+    let timeout = std::time::Duration::from_secs(args.timeout);
+    providers::portal::get_current_location(args.accuracy.as_deref(), timeout, args.verbose)
+        .await
+        .map_err(|e| Error {
+            message: e.to_string(),
+            code: 1,
+        })
+}
+
+async fn try_geoclue(args: &Args) -> Result<Location, Error> {
     use zbus::Connection;
 
     // Connect to system bus
@@ -82,23 +290,24 @@ async fn try_geoclue() -> Result<(f64, f64), Error> {
     })?;
 
     // Get location with timeout
-    let location_path = tokio::time::timeout(std::time::Duration::from_secs(5), async {
-        // Poll for location
-        for _ in 0..10 {
-            if let Ok(path) = client.location().await {
-                if !path.as_str().is_empty() {
-                    return Ok(path);
+    let location_path =
+        tokio::time::timeout(std::time::Duration::from_secs(args.timeout), async {
+            // Poll for location
+            for _ in 0..10 {
+                if let Ok(path) = client.location().await {
+                    if !path.as_str().is_empty() {
+                        return Ok(path);
+                    }
                 }
+                tokio::time::sleep(std::time::Duration::from_millis(500)).await;
             }
-            tokio::time::sleep(std::time::Duration::from_millis(500)).await;
-        }
-        Err(Error::timeout())
-    })
-    .await
-    .map_err(|_| Error::timeout())??;
+            Err(Error::timeout())
+        })
+        .await
+        .map_err(|_| Error::timeout())??;
 
     // Read coordinates
-    let location = LocationProxy::builder(&connection)
+    let location = GeoClueLocationProxy::builder(&connection)
         .path(&location_path)
         .map_err(|_| Error::service_unavailable())?
         .build()
@@ -113,17 +322,42 @@ async fn try_geoclue() -> Result<(f64, f64), Error> {
         .longitude()
         .await
         .map_err(|_| Error::service_unavailable())?;
+    let accuracy = location.accuracy().await.ok();
 
     // Stop client
     let _ = client.stop().await;
 
-    Ok((lat, lon))
+    Ok(Location::new(lat, lon, accuracy, "geoclue", Utc::now()))
+}
+
+async fn try_mmdb(args: &Args) -> Result<Location, Error> {
+    use providers::mmdb::MmdbError;
+
+    let timeout = std::time::Duration::from_secs(args.timeout);
+    providers::mmdb::get_current_location(&args.mmdb, args.ip.as_deref(), timeout, args.verbose)
+        .await
+        .map_err(|e| match e {
+            MmdbError::DatabaseMissing(_) => Error {
+                message: e.to_string(),
+                code: 70,
+            },
+            _ => Error {
+                message: e.to_string(),
+                code: 1,
+            },
+        })
 }
 
-async fn try_ip_location() -> Result<(f64, f64), Error> {
+async fn try_ip_location(ip: Option<&str>) -> Result<Location, Error> {
+    // ip-api.com geolocates a specific address at /json/<ip>, or the caller's
+    // own address at /json.
+    let url = match ip {
+        Some(ip) => format!("http://ip-api.com/json/{ip}"),
+        None => "http://ip-api.com/json".to_string(),
+    };
     let client = reqwest::Client::new();
     let response: serde_json::Value = client
-        .get("http://ip-api.com/json")
+        .get(&url)
         .timeout(std::time::Duration::from_secs(5))
         .send()
         .await
@@ -132,10 +366,10 @@ async fn try_ip_location() -> Result<(f64, f64), Error> {
         .await
         .map_err(|_| Error::network())?;
 
-    let lat = response["lat"].as_f64().ok_or_else(|| Error::network())?;
-    let lon = response["lon"].as_f64().ok_or_else(|| Error::network())?;
+    let lat = response["lat"].as_f64().ok_or_else(Error::network)?;
+    let lon = response["lon"].as_f64().ok_or_else(Error::network)?;
 
-    Ok((lat, lon))
+    Ok(Location::new(lat, lon, None, "ip", Utc::now()))
 }
 
 // Simple error type
@@ -211,10 +445,52 @@ trait Client {
     interface = "org.freedesktop.GeoClue2.Location",
     default_service = "org.freedesktop.GeoClue2"
 )]
-trait Location {
+trait GeoClueLocation {
     #[dbus_proxy(property)]
     fn latitude(&self) -> zbus::Result<f64>;
 
     #[dbus_proxy(property)]
     fn longitude(&self) -> zbus::Result<f64>;
+
+    #[dbus_proxy(property)]
+    fn accuracy(&self) -> zbus::Result<f64>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn accuracy_target_parses_numbers_and_levels() {
+        assert_eq!(accuracy_target_m(Some("250")), Some(250.0));
+        assert_eq!(accuracy_target_m(Some("street")), Some(100.0));
+        assert_eq!(accuracy_target_m(Some("CITY")), Some(5_000.0));
+        assert_eq!(accuracy_target_m(Some("bogus")), None);
+        assert_eq!(accuracy_target_m(None), None);
+    }
+
+    #[test]
+    fn ip_without_accuracy_gets_coarse_radius() {
+        let loc = Location::new(0.0, 0.0, None, "ip", Utc::now());
+        assert_eq!(effective_accuracy(&loc), 50_000.0);
+    }
+
+    #[test]
+    fn explicit_accuracy_wins_over_default() {
+        let loc = Location::new(0.0, 0.0, Some(12.0), "geoclue", Utc::now());
+        assert_eq!(effective_accuracy(&loc), 12.0);
+    }
+
+    #[test]
+    fn missing_accuracy_non_ip_sorts_worst() {
+        let loc = Location::new(0.0, 0.0, None, "portal", Utc::now());
+        assert_eq!(effective_accuracy(&loc), f64::MAX);
+    }
+
+    #[test]
+    fn provider_key_is_stable() {
+        assert_eq!(provider_key(&Provider::Auto), "auto");
+        assert_eq!(provider_key(&Provider::Mmdb), "mmdb");
+    }
 }
@@ -0,0 +1,91 @@
+use crate::location::Location;
+use chrono::Utc;
+use maxminddb::geoip2;
+use std::error::Error;
+use std::fmt;
+use std::net::IpAddr;
+use std::path::Path;
+use std::time::Duration;
+
+#[derive(Debug)]
+pub enum MmdbError {
+    DatabaseMissing(String),
+    Open(String),
+    Lookup(String),
+    NoCoordinates,
+}
+
+impl fmt::Display for MmdbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MmdbError::DatabaseMissing(path) => {
+                write!(f, "MaxMind database not found at {path}")
+            }
+            MmdbError::Open(reason) => write!(f, "failed to open MaxMind database: {reason}"),
+            MmdbError::Lookup(reason) => write!(f, "MaxMind lookup failed: {reason}"),
+            MmdbError::NoCoordinates => write!(f, "no coordinates in MaxMind record"),
+        }
+    }
+}
+
+impl Error for MmdbError {}
+
+/// Resolve coordinates from a local MaxMind City database, no network required.
+///
+/// `ip` is the public address to look up. Pass one (via `--ip`) to stay fully
+/// offline; when it is `None` we make a single cheap network request to
+/// discover the public address, so the air-gapped guarantee only holds with an
+/// explicit `--ip`. The record's `accuracy_radius` (kilometres) is mapped to
+/// `accuracy_m` by multiplying by 1000.
+pub async fn get_current_location(
+    db_path: &str,
+    ip: Option<&str>,
+    timeout: Duration,
+    verbose: bool,
+) -> Result<Location, MmdbError> {
+    if !Path::new(db_path).exists() {
+        return Err(MmdbError::DatabaseMissing(db_path.to_string()));
+    }
+
+    let addr: IpAddr = match ip {
+        Some(raw) => raw
+            .parse()
+            .map_err(|_| MmdbError::Lookup(format!("invalid IP address: {raw}")))?,
+        None => public_ip(timeout).await?,
+    };
+
+    if verbose {
+        eprintln!("geo-loc: resolving {addr} against {db_path}");
+    }
+
+    let reader = maxminddb::Reader::open_mmap(db_path)
+        .map_err(|e| MmdbError::Open(e.to_string()))?;
+    let city: geoip2::City = reader
+        .lookup(addr)
+        .map_err(|e| MmdbError::Lookup(e.to_string()))?;
+
+    let location = city.location.ok_or(MmdbError::NoCoordinates)?;
+    let lat = location.latitude.ok_or(MmdbError::NoCoordinates)?;
+    let lon = location.longitude.ok_or(MmdbError::NoCoordinates)?;
+    // accuracy_radius is reported in kilometres.
+    let accuracy = location.accuracy_radius.map(|km| f64::from(km) * 1000.0);
+
+    Ok(Location::new(lat, lon, accuracy, "mmdb", Utc::now()))
+}
+
+async fn public_ip(timeout: Duration) -> Result<IpAddr, MmdbError> {
+    let client = reqwest::Client::new();
+    let body = client
+        .get("https://api.ipify.org")
+        .timeout(timeout)
+        .send()
+        .await
+        .map_err(|e| MmdbError::Lookup(e.to_string()))?
+        .text()
+        .await
+        .map_err(|e| MmdbError::Lookup(e.to_string()))?;
+
+    body.trim()
+        .parse()
+        .map_err(|_| MmdbError::Lookup(format!("could not parse public IP: {body}")))
+}
@@ -1,3 +1,8 @@
+pub mod mmdb;
+
+#[cfg(target_os = "linux")]
+pub mod portal;
+
 #[cfg(target_os = "macos")]
 pub mod corelocation;
 
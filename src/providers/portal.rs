@@ -0,0 +1,238 @@
+use crate::location::Location;
+use chrono::Utc;
+use futures_util::StreamExt;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+use zbus::Connection;
+use zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value};
+
+#[derive(Debug)]
+pub enum PortalError {
+    Unavailable,
+    Timeout,
+    Failed(String),
+}
+
+impl fmt::Display for PortalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PortalError::Unavailable => {
+                write!(f, "desktop portal location interface unavailable")
+            }
+            PortalError::Timeout => write!(f, "timed out waiting for portal location update"),
+            PortalError::Failed(reason) => write!(f, "portal error: {reason}"),
+        }
+    }
+}
+
+impl Error for PortalError {}
+
+/// Request a single fix from `org.freedesktop.portal.Location` on the session
+/// bus. Works inside Flatpak/Snap sandboxes where talking to GeoClue directly
+/// on the system bus is blocked.
+///
+/// `accuracy` is the `--accuracy` value, mapped to the portal's 0..=5 scale
+/// (0 = None .. 5 = Exact); anything unrecognised requests Exact.
+pub async fn get_current_location(
+    accuracy: Option<&str>,
+    timeout: Duration,
+    verbose: bool,
+) -> Result<Location, PortalError> {
+    let connection = Connection::session()
+        .await
+        .map_err(|_| PortalError::Unavailable)?;
+    let portal = LocationProxy::new(&connection)
+        .await
+        .map_err(|_| PortalError::Unavailable)?;
+
+    if verbose {
+        eprintln!("geo-loc: requesting portal location fix");
+    }
+
+    // Subscribe before starting so we don't miss an early update.
+    let mut updates = portal
+        .receive_location_updated()
+        .await
+        .map_err(|_| PortalError::Unavailable)?;
+
+    // CreateSession returns a Request handle, not the session; the real session
+    // handle arrives on that request's Response signal. The tokens let us
+    // predict the Request object paths so we can subscribe to Response *before*
+    // issuing the call, avoiding the race where a fast reply is missed.
+    let pid = std::process::id();
+    let create_token = format!("geoloc_create_{pid}");
+    let start_token = format!("geoloc_start_{pid}");
+
+    let create_request = request_proxy(&connection, &create_token).await?;
+    let mut create_responses = create_request
+        .receive_response()
+        .await
+        .map_err(|_| PortalError::Unavailable)?;
+
+    let mut create_opts: HashMap<&str, Value<'_>> = HashMap::new();
+    create_opts.insert("handle_token", Value::from(create_token));
+    create_opts.insert("session_handle_token", Value::from(format!("geoloc_{pid}")));
+    create_opts.insert("accuracy", Value::from(accuracy_level(accuracy)));
+    portal
+        .create_session(create_opts)
+        .await
+        .map_err(|e| PortalError::Failed(e.to_string()))?;
+
+    let results = first_response(&mut create_responses, timeout).await?;
+    let session = results
+        .get("session_handle")
+        .and_then(|v| String::try_from(v).ok())
+        .and_then(|s| ObjectPath::try_from(s).ok().map(OwnedObjectPath::from))
+        .ok_or_else(|| PortalError::Failed("no session_handle in response".into()))?;
+
+    let start_request = request_proxy(&connection, &start_token).await?;
+    let mut start_responses = start_request
+        .receive_response()
+        .await
+        .map_err(|_| PortalError::Unavailable)?;
+
+    let mut start_opts: HashMap<&str, Value<'_>> = HashMap::new();
+    start_opts.insert("handle_token", Value::from(start_token));
+    portal
+        .start(&session, "", start_opts)
+        .await
+        .map_err(|e| PortalError::Failed(e.to_string()))?;
+    // Confirm Start succeeded before waiting for the first update.
+    first_response(&mut start_responses, timeout).await?;
+
+    let fix = tokio::time::timeout(timeout, updates.next())
+        .await
+        .map_err(|_| PortalError::Timeout)?
+        .ok_or(PortalError::Timeout)?;
+
+    let args = fix
+        .args()
+        .map_err(|e| PortalError::Failed(e.to_string()))?;
+    let location = &args.location;
+
+    let lat = dict_f64(location, "Latitude").ok_or(PortalError::Failed("no latitude".into()))?;
+    let lon = dict_f64(location, "Longitude").ok_or(PortalError::Failed("no longitude".into()))?;
+    let accuracy_m = dict_f64(location, "Accuracy").filter(|a| *a >= 0.0);
+
+    // Close the session now that we have our fix. Sessions are closed via
+    // org.freedesktop.portal.Session.Close() on the session object path, not
+    // through the Location interface.
+    if let Ok(builder) = SessionProxy::builder(&connection).path(&session) {
+        if let Ok(session_proxy) = builder.build().await {
+            let _ = session_proxy.close().await;
+        }
+    }
+
+    Ok(Location::new(lat, lon, accuracy_m, "portal", Utc::now()))
+}
+
+/// The Request object path the portal will use for a method call made with
+/// `handle_token`, per the portal naming convention
+/// `/org/freedesktop/portal/desktop/request/<SENDER>/<TOKEN>`.
+fn request_path(connection: &Connection, token: &str) -> Option<OwnedObjectPath> {
+    let unique = connection.unique_name()?;
+    let sender = unique.trim_start_matches(':').replace('.', "_");
+    let path = format!("/org/freedesktop/portal/desktop/request/{sender}/{token}");
+    ObjectPath::try_from(path).ok().map(OwnedObjectPath::from)
+}
+
+/// Build a `Request` proxy at the predicted path so its `Response` signal can
+/// be subscribed to before the originating method call is issued.
+async fn request_proxy<'a>(
+    connection: &'a Connection,
+    token: &str,
+) -> Result<RequestProxy<'a>, PortalError> {
+    let path = request_path(connection, token).ok_or(PortalError::Unavailable)?;
+    RequestProxy::builder(connection)
+        .path(path)
+        .map_err(|_| PortalError::Unavailable)?
+        .build()
+        .await
+        .map_err(|_| PortalError::Unavailable)
+}
+
+/// Pull the first `Response(u, a{sv})` off an already-subscribed stream and
+/// return its results dict, failing if the request was cancelled or timed out.
+async fn first_response<S>(
+    responses: &mut S,
+    timeout: Duration,
+) -> Result<HashMap<String, OwnedValue>, PortalError>
+where
+    S: futures_util::Stream<Item = Response> + Unpin,
+{
+    let signal = tokio::time::timeout(timeout, responses.next())
+        .await
+        .map_err(|_| PortalError::Timeout)?
+        .ok_or(PortalError::Timeout)?;
+    let args = signal
+        .args()
+        .map_err(|e| PortalError::Failed(e.to_string()))?;
+    if args.response != 0 {
+        return Err(PortalError::Failed(format!(
+            "portal request cancelled or failed (response {})",
+            args.response
+        )));
+    }
+    Ok(args.results)
+}
+
+fn accuracy_level(accuracy: Option<&str>) -> u32 {
+    match accuracy.map(|s| s.to_ascii_lowercase()).as_deref() {
+        Some("none") => 0,
+        Some("country") => 1,
+        Some("city") => 2,
+        Some("neighborhood") | Some("neighbourhood") => 3,
+        Some("street") => 4,
+        _ => 5, // Exact
+    }
+}
+
+fn dict_f64(dict: &HashMap<String, OwnedValue>, key: &str) -> Option<f64> {
+    dict.get(key).and_then(|v| f64::try_from(v).ok())
+}
+
+#[zbus::dbus_proxy(
+    interface = "org.freedesktop.portal.Location",
+    default_service = "org.freedesktop.portal.Desktop",
+    default_path = "/org/freedesktop/portal/desktop"
+)]
+trait Location {
+    fn create_session(&self, options: HashMap<&str, Value<'_>>) -> zbus::Result<OwnedObjectPath>;
+
+    fn start(
+        &self,
+        session_handle: &OwnedObjectPath,
+        parent_window: &str,
+        options: HashMap<&str, Value<'_>>,
+    ) -> zbus::Result<OwnedObjectPath>;
+
+    #[dbus_proxy(signal)]
+    fn location_updated(
+        &self,
+        session_handle: OwnedObjectPath,
+        location: HashMap<String, OwnedValue>,
+    ) -> zbus::Result<()>;
+}
+
+#[zbus::dbus_proxy(
+    interface = "org.freedesktop.portal.Session",
+    default_service = "org.freedesktop.portal.Desktop"
+)]
+trait Session {
+    fn close(&self) -> zbus::Result<()>;
+}
+
+#[zbus::dbus_proxy(
+    interface = "org.freedesktop.portal.Request",
+    default_service = "org.freedesktop.portal.Desktop"
+)]
+trait Request {
+    #[dbus_proxy(signal)]
+    fn response(
+        &self,
+        response: u32,
+        results: HashMap<String, OwnedValue>,
+    ) -> zbus::Result<()>;
+}